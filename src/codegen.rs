@@ -0,0 +1,108 @@
+//! Emit the packed bitstream as embeddable Rust or C source instead of a raw blob.
+
+use crate::errors::*;
+use clap::ValueEnum;
+use std::io::Write;
+
+/// How the packed bytes should be written to the output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Write the raw packed bytes
+    #[default]
+    Raw,
+    /// Write a Rust source file defining a `const` byte array
+    Rust,
+    /// Write a C header defining a `static const` byte array
+    #[value(name = "c-header")]
+    CHeader,
+}
+
+/// Number of bytes per line when no row stride applies (e.g. unaligned rows).
+const BYTES_PER_LINE: usize = 12;
+
+/// Write `bytes` to `writer` using `format`. The source formats name the array
+/// `name` and emit `width`/`height` as sibling constants; `Raw` writes only
+/// the bytes.
+///
+/// `row_stride`, if set, groups the array literal into lines matching the
+/// image width, one source line per packed pixel row.
+pub fn write<W: Write>(
+    bytes: &[u8],
+    width: u32,
+    height: u32,
+    row_stride: Option<usize>,
+    name: &str,
+    format: OutputFormat,
+    writer: &mut W,
+) -> Result<()> {
+    match format {
+        OutputFormat::Raw => writer.write_all(bytes)?,
+        OutputFormat::Rust => write_rust(bytes, width, height, row_stride, name, writer)?,
+        OutputFormat::CHeader => write_c_header(bytes, width, height, row_stride, name, writer)?,
+    }
+    Ok(())
+}
+
+fn write_byte_rows<W: Write>(
+    bytes: &[u8],
+    row_stride: Option<usize>,
+    writer: &mut W,
+) -> Result<()> {
+    let chunk_size = row_stride.filter(|n| *n > 0).unwrap_or(BYTES_PER_LINE);
+    for row in bytes.chunks(chunk_size) {
+        write!(writer, "   ")?;
+        for byte in row {
+            write!(writer, " 0x{byte:02x},")?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+fn write_rust<W: Write>(
+    bytes: &[u8],
+    width: u32,
+    height: u32,
+    row_stride: Option<usize>,
+    name: &str,
+    writer: &mut W,
+) -> Result<()> {
+    writeln!(
+        writer,
+        "// Generated by embedded-mono-img, do not edit manually"
+    )?;
+    writeln!(writer, "pub const {name}_WIDTH: u32 = {width};")?;
+    writeln!(writer, "pub const {name}_HEIGHT: u32 = {height};")?;
+    writeln!(writer, "pub const {name}: [u8; {}] = [", bytes.len())?;
+    write_byte_rows(bytes, row_stride, writer)?;
+    writeln!(writer, "];")?;
+    Ok(())
+}
+
+fn write_c_header<W: Write>(
+    bytes: &[u8],
+    width: u32,
+    height: u32,
+    row_stride: Option<usize>,
+    name: &str,
+    writer: &mut W,
+) -> Result<()> {
+    let guard = format!("{}_H", name.to_uppercase());
+    writeln!(
+        writer,
+        "// Generated by embedded-mono-img, do not edit manually"
+    )?;
+    writeln!(writer, "#ifndef {guard}")?;
+    writeln!(writer, "#define {guard}")?;
+    writeln!(writer)?;
+    writeln!(writer, "#include <stdint.h>")?;
+    writeln!(writer)?;
+    writeln!(writer, "#define {name}_WIDTH {width}")?;
+    writeln!(writer, "#define {name}_HEIGHT {height}")?;
+    writeln!(writer, "static const uint8_t {name}[{}] = {{", bytes.len())?;
+    write_byte_rows(bytes, row_stride, writer)?;
+    writeln!(writer, "}};")?;
+    writeln!(writer)?;
+    writeln!(writer, "#endif // {guard}")?;
+    Ok(())
+}
@@ -0,0 +1,175 @@
+//! Lossless compression of the packed 1bpp bitstream.
+
+use crate::errors::*;
+use clap::ValueEnum;
+#[cfg(feature = "deflate")]
+use std::io::{Read, Write};
+
+/// Compression scheme applied to the packed bitstream before it's written out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    /// Don't compress, write the packed bytes as-is
+    #[default]
+    None,
+    /// PackBits RLE, cheap and effective on long runs of 0x00/0xFF
+    Packbits,
+    /// DEFLATE via flate2 (requires the `deflate` feature)
+    Deflate,
+}
+
+/// Compress `bytes` according to `compression`.
+pub fn compress(bytes: &[u8], compression: Compression) -> Result<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(bytes.to_vec()),
+        Compression::Packbits => Ok(packbits::encode(bytes)),
+        Compression::Deflate => deflate_encode(bytes),
+    }
+}
+
+/// Reverse [`compress`], returning the original packed bytes.
+pub fn decompress(bytes: &[u8], compression: Compression) -> Result<Vec<u8>> {
+    match compression {
+        Compression::None => Ok(bytes.to_vec()),
+        Compression::Packbits => packbits::decode(bytes),
+        Compression::Deflate => deflate_decode(bytes),
+    }
+}
+
+#[cfg(feature = "deflate")]
+fn deflate_encode(bytes: &[u8]) -> Result<Vec<u8>> {
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression as Flate2Compression;
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Flate2Compression::default());
+    encoder
+        .write_all(bytes)
+        .context("Failed to deflate-compress bitstream")?;
+    encoder
+        .finish()
+        .context("Failed to finish deflate compression")
+}
+
+#[cfg(not(feature = "deflate"))]
+fn deflate_encode(_bytes: &[u8]) -> Result<Vec<u8>> {
+    bail!("This binary was built without the `deflate` feature");
+}
+
+#[cfg(feature = "deflate")]
+fn deflate_decode(bytes: &[u8]) -> Result<Vec<u8>> {
+    use flate2::read::DeflateDecoder;
+
+    let mut decoder = DeflateDecoder::new(bytes);
+    let mut out = Vec::new();
+    decoder
+        .read_to_end(&mut out)
+        .context("Failed to deflate-decompress bitstream")?;
+    Ok(out)
+}
+
+#[cfg(not(feature = "deflate"))]
+fn deflate_decode(_bytes: &[u8]) -> Result<Vec<u8>> {
+    bail!("This binary was built without the `deflate` feature");
+}
+
+/// A minimal PackBits RLE implementation.
+///
+/// Packet layout: a header byte `n` in `0..=127` means "copy the next `n+1`
+/// literal bytes"; a header byte in `129..=255` (representing `-1..=-127`,
+/// stored as `257-count`) means "repeat the next single byte `count`
+/// times"; `0x80` is a no-op and carries no payload.
+mod packbits {
+    use super::*;
+
+    const NOOP: u8 = 0x80;
+    const MAX_RUN: usize = 128;
+
+    pub fn encode(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let run_len = run_length(data, i);
+            if run_len >= 2 {
+                out.push((257 - run_len) as u8);
+                out.push(data[i]);
+                i += run_len;
+            } else {
+                let start = i;
+                i += 1;
+                while i < data.len() && i - start < MAX_RUN && run_length(data, i) < 2 {
+                    i += 1;
+                }
+                out.push((i - start - 1) as u8);
+                out.extend_from_slice(&data[start..i]);
+            }
+        }
+        out
+    }
+
+    fn run_length(data: &[u8], start: usize) -> usize {
+        let mut len = 1;
+        while start + len < data.len() && data[start + len] == data[start] && len < MAX_RUN {
+            len += 1;
+        }
+        len
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let header = data[i];
+            i += 1;
+            if header == NOOP {
+                continue;
+            }
+            let header = header as i8;
+            if header >= 0 {
+                let n = header as usize + 1;
+                let bytes = data
+                    .get(i..i + n)
+                    .context("Truncated PackBits literal packet")?;
+                out.extend_from_slice(bytes);
+                i += n;
+            } else {
+                let count = (1 - header as i32) as usize;
+                let byte = *data.get(i).context("Truncated PackBits repeat packet")?;
+                i += 1;
+                out.extend(std::iter::repeat_n(byte, count));
+            }
+        }
+        Ok(out)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_roundtrip_empty() {
+            assert_eq!(decode(&encode(&[])).unwrap(), Vec::<u8>::new());
+        }
+
+        #[test]
+        fn test_roundtrip_run() {
+            let data = vec![0x00; 300];
+            let encoded = encode(&data);
+            assert!(encoded.len() < data.len());
+            assert_eq!(decode(&encoded).unwrap(), data);
+        }
+
+        #[test]
+        fn test_roundtrip_literals() {
+            let data = vec![0x01, 0x02, 0x03, 0x04, 0x05];
+            assert_eq!(decode(&encode(&data)).unwrap(), data);
+        }
+
+        #[test]
+        fn test_roundtrip_mixed() {
+            let mut data = vec![0xFF; 10];
+            data.extend([0x01, 0x02, 0x03]);
+            data.extend(vec![0x00; 200]);
+            data.extend([0x42]);
+            assert_eq!(decode(&encode(&data)).unwrap(), data);
+        }
+    }
+}
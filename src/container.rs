@@ -0,0 +1,212 @@
+//! A small self-describing container format: a fixed binary header
+//! prepended to the packed bitstream so consumers don't need to know the
+//! image dimensions or packing options out-of-band.
+
+use crate::compression::Compression;
+use crate::errors::*;
+use crate::pack::{BitOrder, Layout};
+use std::io::Write;
+
+/// Identifies an embedded-mono-img container.
+pub const MAGIC: [u8; 4] = *b"EMIM";
+/// Container format version written by this crate.
+pub const VERSION: u8 = 1;
+/// Size in bytes of the header: magic + version + flags + width + height.
+pub const HEADER_LEN: usize = 4 + 1 + 1 + 2 + 2;
+
+/// A parsed container header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    /// Whether rows were flushed to a fresh byte (`!no_flush_after_pixel_row`)
+    pub row_aligned: bool,
+    pub bit_order: BitOrder,
+    pub layout: Layout,
+    pub compression: Compression,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// Write the fixed-size binary encoding of `header` ([`HEADER_LEN`] bytes).
+pub fn write_header<W: Write>(header: &Header, writer: &mut W) -> Result<()> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&[header.version])?;
+    writer.write_all(&[encode_flags(header)])?;
+    writer.write_all(&header.width.to_be_bytes())?;
+    writer.write_all(&header.height.to_be_bytes())?;
+    Ok(())
+}
+
+/// Parse a [`Header`] off the front of `data`, returning it along with the
+/// remaining payload.
+pub fn parse_header(data: &[u8]) -> Result<(Header, &[u8])> {
+    ensure!(
+        data.len() >= HEADER_LEN,
+        "Input too short to contain a container header"
+    );
+    let (head, rest) = data.split_at(HEADER_LEN);
+
+    ensure!(
+        head[0..4] == MAGIC,
+        "Bad magic bytes, not an embedded-mono-img container"
+    );
+    let version = head[4];
+    ensure!(
+        version == VERSION,
+        "Unsupported container version: {version}"
+    );
+
+    let (row_aligned, bit_order, layout, compression) = decode_flags(head[5])?;
+    let width = u16::from_be_bytes([head[6], head[7]]);
+    let height = u16::from_be_bytes([head[8], head[9]]);
+
+    let header = Header {
+        version,
+        row_aligned,
+        bit_order,
+        layout,
+        compression,
+        width,
+        height,
+    };
+    Ok((header, rest))
+}
+
+/// The number of bytes the packed (but not compressed) payload described by
+/// `header` takes up, so embedded readers can pre-allocate.
+pub fn required_bytes(header: &Header) -> usize {
+    let width = header.width as usize;
+    let height = header.height as usize;
+    match header.layout {
+        Layout::Horizontal if header.row_aligned => width.div_ceil(8) * height,
+        Layout::Horizontal => (width * height).div_ceil(8),
+        Layout::VerticalPages => width * height.div_ceil(8),
+    }
+}
+
+const FLAG_ROW_ALIGNED: u8 = 0b0000_0001;
+const FLAG_BIT_ORDER_LSB: u8 = 0b0000_0010;
+const FLAG_LAYOUT_VERTICAL_PAGES: u8 = 0b0000_0100;
+const FLAG_COMPRESSION_SHIFT: u8 = 3;
+const FLAG_COMPRESSION_MASK: u8 = 0b0001_1000;
+
+fn encode_flags(header: &Header) -> u8 {
+    let mut flags = 0;
+    if header.row_aligned {
+        flags |= FLAG_ROW_ALIGNED;
+    }
+    if header.bit_order == BitOrder::Lsb {
+        flags |= FLAG_BIT_ORDER_LSB;
+    }
+    if header.layout == Layout::VerticalPages {
+        flags |= FLAG_LAYOUT_VERTICAL_PAGES;
+    }
+    let compression = match header.compression {
+        Compression::None => 0,
+        Compression::Packbits => 1,
+        Compression::Deflate => 2,
+    };
+    flags |= compression << FLAG_COMPRESSION_SHIFT;
+    flags
+}
+
+fn decode_flags(flags: u8) -> Result<(bool, BitOrder, Layout, Compression)> {
+    let row_aligned = flags & FLAG_ROW_ALIGNED != 0;
+    let bit_order = if flags & FLAG_BIT_ORDER_LSB != 0 {
+        BitOrder::Lsb
+    } else {
+        BitOrder::Msb
+    };
+    let layout = if flags & FLAG_LAYOUT_VERTICAL_PAGES != 0 {
+        Layout::VerticalPages
+    } else {
+        Layout::Horizontal
+    };
+    let compression = match (flags & FLAG_COMPRESSION_MASK) >> FLAG_COMPRESSION_SHIFT {
+        0 => Compression::None,
+        1 => Compression::Packbits,
+        2 => Compression::Deflate,
+        n => bail!("Unknown compression flag bits: {n}"),
+    };
+    Ok((row_aligned, bit_order, layout, compression))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_header() -> Header {
+        Header {
+            version: VERSION,
+            row_aligned: true,
+            bit_order: BitOrder::Lsb,
+            layout: Layout::VerticalPages,
+            compression: Compression::Packbits,
+            width: 24,
+            height: 14,
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let header = sample_header();
+        let mut buf = Vec::new();
+        write_header(&header, &mut buf).unwrap();
+        buf.extend_from_slice(b"payload");
+
+        let (parsed, rest) = parse_header(&buf).unwrap();
+        assert_eq!(parsed, header);
+        assert_eq!(rest, b"payload");
+    }
+
+    #[test]
+    fn test_rejects_bad_magic() {
+        let mut buf = Vec::new();
+        write_header(&sample_header(), &mut buf).unwrap();
+        buf[0] = b'X';
+        assert!(parse_header(&buf).is_err());
+    }
+
+    #[test]
+    fn test_rejects_truncated_input() {
+        assert!(parse_header(&[0; HEADER_LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn test_required_bytes_horizontal_row_aligned() {
+        let header = Header {
+            layout: Layout::Horizontal,
+            row_aligned: true,
+            width: 30,
+            height: 14,
+            ..sample_header()
+        };
+        // ceil(30/8) * 14 = 4 * 14
+        assert_eq!(required_bytes(&header), 56);
+    }
+
+    #[test]
+    fn test_required_bytes_horizontal_unaligned() {
+        let header = Header {
+            layout: Layout::Horizontal,
+            row_aligned: false,
+            width: 30,
+            height: 14,
+            ..sample_header()
+        };
+        // ceil(30*14/8) = ceil(420/8) = 53
+        assert_eq!(required_bytes(&header), 53);
+    }
+
+    #[test]
+    fn test_required_bytes_vertical_pages() {
+        let header = Header {
+            layout: Layout::VerticalPages,
+            width: 30,
+            height: 14,
+            ..sample_header()
+        };
+        // 30 columns * ceil(14/8) pages = 30 * 2
+        assert_eq!(required_bytes(&header), 60);
+    }
+}
@@ -0,0 +1,76 @@
+//! Floyd–Steinberg error-diffusion dithering, as an alternative to flat
+//! thresholding for gradients/photos on 1bpp displays.
+
+use crate::Image;
+use clap::ValueEnum;
+
+/// How `process_image` should decide whether a pixel is on or off.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Dither {
+    /// Flat per-pixel compare against the threshold (the default)
+    #[default]
+    None,
+    /// Floyd–Steinberg error diffusion
+    #[value(name = "floyd-steinberg")]
+    FloydSteinberg,
+}
+
+/// Dither `image` with Floyd–Steinberg error diffusion, returning one bit
+/// per pixel (`true` = on) in row-major order.
+///
+/// With `serpentine` set, odd rows are scanned right-to-left and the
+/// diffusion weights are mirrored accordingly, to reduce the directional
+/// streaking plain left-to-right scanning produces.
+pub fn floyd_steinberg(image: &Image, serpentine: bool) -> Vec<bool> {
+    let width = image.width() as usize;
+    let height = image.height() as usize;
+    let mut errors: Vec<i16> = image.pixels().map(|px| px.0[0] as i16).collect();
+    let mut bits = vec![false; width * height];
+
+    for y in 0..height {
+        let reverse = serpentine && y % 2 == 1;
+        let xs: Box<dyn Iterator<Item = usize>> = if reverse {
+            Box::new((0..width).rev())
+        } else {
+            Box::new(0..width)
+        };
+
+        // Direction of travel along the row; the diffusion weights mirror
+        // around it on a reversed (serpentine) row.
+        let dx: i32 = if reverse { -1 } else { 1 };
+
+        for x in xs {
+            let idx = y * width + x;
+            let value = errors[idx];
+            let out: i16 = if value >= 128 { 255 } else { 0 };
+            bits[idx] = out != 0;
+            let err = value - out;
+
+            diffuse(&mut errors, width, height, x, y, dx, 0, err * 7 / 16);
+            diffuse(&mut errors, width, height, x, y, -dx, 1, err * 3 / 16);
+            diffuse(&mut errors, width, height, x, y, 0, 1, err * 5 / 16);
+            diffuse(&mut errors, width, height, x, y, dx, 1, err / 16);
+        }
+    }
+
+    bits
+}
+
+#[allow(clippy::too_many_arguments)]
+fn diffuse(
+    errors: &mut [i16],
+    width: usize,
+    height: usize,
+    x: usize,
+    y: usize,
+    dx: i32,
+    dy: i32,
+    amount: i16,
+) {
+    let nx = x as i32 + dx;
+    let ny = y as i32 + dy;
+    if nx < 0 || ny < 0 || nx as usize >= width || ny as usize >= height {
+        return;
+    }
+    errors[ny as usize * width + nx as usize] += amount;
+}
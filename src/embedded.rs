@@ -0,0 +1,75 @@
+//! `no_std`, alloc-free bitstream unpacking for on-device use.
+//!
+//! Mirrors the default packing layout produced by the host-side `Pack`
+//! writer (horizontal layout, MSB-first bit order): one bit per pixel, in
+//! row-major order. When rows are flushed (`no_flush_after_pixel_row = false`,
+//! the default) each row starts on a fresh byte and the stride is
+//! `ceil(width / 8)` bytes, with the tail bits of the last byte in each row
+//! padded; with `no_flush_after_pixel_row` set, bits run contiguously across
+//! row boundaries and only the final byte is padded. Bitstreams packed with
+//! LSB-first bit order or the vertical-pages layout are not handled.
+
+/// Iterates the pixels encoded in a horizontal, MSB-first packed bitstream,
+/// in the exact order `Pack` produced them.
+pub struct UnpackReader<'a> {
+    data: &'a [u8],
+    width: u32,
+    height: u32,
+    no_flush_after_pixel_row: bool,
+    x: u32,
+    y: u32,
+    bit: usize,
+}
+
+impl<'a> UnpackReader<'a> {
+    /// Create a reader over `data`, a packed bitstream for an image of
+    /// `width` x `height` pixels. `no_flush_after_pixel_row` must match the
+    /// setting used to produce `data`.
+    pub fn new(data: &'a [u8], width: u32, height: u32, no_flush_after_pixel_row: bool) -> Self {
+        UnpackReader {
+            data,
+            width,
+            height,
+            no_flush_after_pixel_row,
+            x: 0,
+            y: 0,
+            bit: 0,
+        }
+    }
+
+    fn row_stride(&self) -> usize {
+        (self.width as usize).div_ceil(8)
+    }
+
+    fn next_bit(&self) -> Option<bool> {
+        if self.no_flush_after_pixel_row {
+            let byte = *self.data.get(self.bit / 8)?;
+            Some((byte >> (7 - (self.bit % 8))) & 1 != 0)
+        } else {
+            let byte_idx = self.y as usize * self.row_stride() + (self.x as usize / 8);
+            let byte = *self.data.get(byte_idx)?;
+            Some((byte >> (7 - (self.x as usize % 8))) & 1 != 0)
+        }
+    }
+}
+
+impl<'a> Iterator for UnpackReader<'a> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<bool> {
+        if self.y >= self.height {
+            return None;
+        }
+
+        let bit = self.next_bit()?;
+
+        self.bit += 1;
+        self.x += 1;
+        if self.x >= self.width {
+            self.x = 0;
+            self.y += 1;
+        }
+
+        Some(bit)
+    }
+}
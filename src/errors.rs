@@ -0,0 +1,2 @@
+pub use anyhow::{anyhow, bail, ensure, Context, Error, Result};
+pub use log::{debug, error, info, trace, warn};
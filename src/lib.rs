@@ -0,0 +1,20 @@
+//! Library half of embedded-mono-img.
+//!
+//! Builds the host-side helpers used by the CLI (output codegen, bitstream
+//! compression, the container format) unconditionally. Enabling the
+//! `embedded` feature additionally builds [`embedded`], a `no_std`,
+//! alloc-free module for unpacking the packed bitstream on-device; it's
+//! written against only `core` so firmware can vendor just that module.
+
+pub mod codegen;
+pub mod compression;
+pub mod container;
+pub mod dither;
+pub mod errors;
+pub mod pack;
+
+/// A decoded, grayscale source image, prior to thresholding/dithering and packing.
+pub type Image = image::ImageBuffer<image::Luma<u8>, Vec<u8>>;
+
+#[cfg(feature = "embedded")]
+pub mod embedded;
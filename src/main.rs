@@ -1,9 +1,13 @@
-mod errors;
-
-use crate::errors::*;
 use clap::{ArgAction, Parser};
+use embedded_mono_img::codegen::{self, OutputFormat};
+use embedded_mono_img::compression::{self, Compression};
+use embedded_mono_img::container::{self, Header};
+use embedded_mono_img::dither::{self, Dither};
+use embedded_mono_img::errors::*;
+use embedded_mono_img::pack::{BitOrder, Layout, Pack};
+use embedded_mono_img::Image;
 use env_logger::Env;
-use image::{ImageBuffer, ImageFormat, ImageReader, Luma};
+use image::{ImageFormat, ImageReader};
 use std::fs::File;
 use std::io::{self, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
@@ -23,6 +27,34 @@ pub struct Args {
     /// The path to write the output to (- for stdout)
     #[arg(short, long)]
     output: PathBuf,
+    /// Output format to write the packed bitstream as
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+    /// Identifier to use for the generated array (and derived constants)
+    #[arg(long, default_value = "IMAGE")]
+    name: String,
+    /// Compression to apply to the packed bitstream
+    #[arg(long, value_enum, default_value_t)]
+    compression: Compression,
+    /// Decompress the input instead of converting an image (for round-trip verification)
+    #[arg(long)]
+    decompress: bool,
+    /// Dithering mode to use instead of flat thresholding
+    #[arg(long, value_enum, default_value_t)]
+    dither: Dither,
+    /// Reverse scan direction on odd rows when dithering, to reduce directional artifacts
+    #[arg(long)]
+    serpentine: bool,
+    /// How pixels are grouped into bytes
+    #[arg(long, value_enum, default_value_t)]
+    layout: Layout,
+    /// Bit order within each packed byte [default: lsb for vertical-pages
+    /// (SSD1306/SH1106 GDDRAM order), msb otherwise]
+    #[arg(long, value_enum)]
+    bit_order: Option<BitOrder>,
+    /// Prepend a self-describing container header (magic, version, flags, dimensions)
+    #[arg(long)]
+    header: bool,
     /// The path to read the image from (- for stdin)
     input: PathBuf,
 }
@@ -30,6 +62,10 @@ pub struct Args {
 pub struct Settings {
     threshold: u8,
     no_flush_after_pixel_row: bool,
+    dither: Dither,
+    serpentine: bool,
+    layout: Layout,
+    bit_order: BitOrder,
 }
 
 impl From<&Args> for Settings {
@@ -37,73 +73,20 @@ impl From<&Args> for Settings {
         Self {
             threshold: args.threshold,
             no_flush_after_pixel_row: args.no_flush_after_pixel_row,
+            dither: args.dither,
+            serpentine: args.serpentine,
+            layout: args.layout,
+            bit_order: args.bit_order.unwrap_or(match args.layout {
+                // SSD1306/SH1106 GDDRAM expects bit Dk of a page byte to hold
+                // pixel row `page*8+k`, i.e. the first bit packed is the
+                // least significant one.
+                Layout::VerticalPages => BitOrder::Lsb,
+                Layout::Horizontal => BitOrder::Msb,
+            }),
         }
     }
 }
 
-pub type Image = ImageBuffer<Luma<u8>, Vec<u8>>;
-
-struct Pack<W> {
-    writer: W,
-    bits: [u8; 8],
-    ctr: usize,
-}
-
-impl<W: io::Write> Pack<W> {
-    pub fn new(writer: W) -> Self {
-        Pack {
-            writer,
-            bits: Default::default(),
-            ctr: 0,
-        }
-    }
-
-    fn clear(&mut self) {
-        self.bits = Default::default();
-        self.ctr = 0;
-    }
-
-    fn to_byte(&self) -> u8 {
-        let mut byte = 0;
-        for (ctr, bit) in self.bits.iter().enumerate() {
-            if ctr > 0 {
-                byte <<= 1;
-            }
-            byte |= bit;
-        }
-        byte
-    }
-
-    fn write(&mut self) -> Result<()> {
-        let byte = self.to_byte();
-        debug!("Writing byte to file: 0x{byte:02X}");
-        self.writer.write_all(&[byte])?;
-        self.clear();
-        Ok(())
-    }
-
-    pub fn add(&mut self, bit: u8) -> Result<()> {
-        self.bits[self.ctr] = bit;
-        self.ctr += 1;
-        if self.ctr >= self.bits.len() {
-            self.write()?;
-        }
-        Ok(())
-    }
-
-    pub fn flush(&mut self) -> Result<()> {
-        if self.ctr == 0 {
-            return Ok(());
-        }
-        debug!("Padding incomplete byte with false-y bits");
-        self.write()
-    }
-
-    pub fn into_inner(self) -> W {
-        self.writer
-    }
-}
-
 pub fn load_image<R: io::BufRead + io::Seek>(reader: R) -> Result<Image> {
     let reader = ImageReader::with_format(reader, ImageFormat::Png);
     let image = reader.decode().context("Failed to decode png image")?;
@@ -116,18 +99,49 @@ pub fn process_image<W: io::Write>(
     output: &mut W,
     settings: &Settings,
 ) -> Result<()> {
-    let mut pack = Pack::new(output);
+    let width = gray_image.width() as usize;
+    let height = gray_image.height() as usize;
+
+    // Decide which pixels are on, flattened to row-major order
+    let bits: Vec<bool> = match settings.dither {
+        Dither::None => gray_image
+            .pixels()
+            .map(|px| px.0[0] > settings.threshold)
+            .collect(),
+        Dither::FloydSteinberg => dither::floyd_steinberg(gray_image, settings.serpentine),
+    };
+
+    let mut pack = Pack::new(output, settings.bit_order);
 
     // Pack 8 pixels into 1 byte
-    for row in gray_image.rows() {
-        for px in row {
-            trace!("pixel = {px:?}");
-            let bit = if px.0[0] > settings.threshold { 1 } else { 0 };
-            pack.add(bit).context("Failed to write to output file")?;
+    match settings.layout {
+        Layout::Horizontal => {
+            for row in bits.chunks(width) {
+                for &bit in row {
+                    trace!("pixel = {bit}");
+                    pack.add(bit as u8)
+                        .context("Failed to write to output file")?;
+                }
+
+                if !settings.no_flush_after_pixel_row {
+                    pack.flush().context("Failed to write to output file")?;
+                }
+            }
         }
-
-        if !settings.no_flush_after_pixel_row {
-            pack.flush().context("Failed to write to output file")?;
+        Layout::VerticalPages => {
+            // Each byte holds 8 vertically stacked pixels of one column
+            // within an 8-row page; rows past the image height in the last
+            // page are padded with off bits.
+            for page in 0..height.div_ceil(8) {
+                for x in 0..width {
+                    for k in 0..8 {
+                        let y = page * 8 + k;
+                        let bit = y < height && bits[y * width + x];
+                        pack.add(bit as u8)
+                            .context("Failed to write to output file")?;
+                    }
+                }
+            }
         }
     }
 
@@ -149,6 +163,25 @@ fn main() -> Result<()> {
     };
     env_logger::init_from_env(Env::default().default_filter_or(log_level));
 
+    if args.decompress {
+        let mut buf = Vec::new();
+        read_input(&args.input, &mut buf)?;
+        // A container header, if present, records its own compression, so
+        // strip it and decompress accordingly rather than running the
+        // (possibly wrong) codec over the header bytes too.
+        let (payload, compression) = match container::parse_header(&buf) {
+            Ok((header, rest)) => (rest, header.compression),
+            Err(_) => (buf.as_slice(), args.compression),
+        };
+        let decompressed =
+            compression::decompress(payload, compression).context("Failed to decompress input")?;
+        let mut output = open_output(&args.output)?;
+        output
+            .write_all(&decompressed)
+            .context("Failed to write output file")?;
+        return Ok(());
+    }
+
     // Read input file
     let gray_image = if args.input == Path::new("-") {
         let mut buf = vec![];
@@ -162,21 +195,98 @@ fn main() -> Result<()> {
         load_image(BufReader::new(file))?
     };
 
-    // Open output file
-    let mut output: Box<dyn Write> = if args.output == Path::new("-") {
-        Box::new(io::stdout())
+    // Open output file (after a successful decode, so a bad input doesn't
+    // clobber a pre-existing output file)
+    let mut output = open_output(&args.output)?;
+
+    // Process image
+    let settings = Settings::from(&args);
+    let mut packed = Vec::new();
+    process_image(&gray_image, &mut packed, &settings)?;
+    let packed =
+        compression::compress(&packed, args.compression).context("Failed to compress bitstream")?;
+
+    let packed = if args.header {
+        let header = Header {
+            version: container::VERSION,
+            row_aligned: !settings.no_flush_after_pixel_row,
+            bit_order: settings.bit_order,
+            layout: args.layout,
+            compression: args.compression,
+            width: gray_image
+                .width()
+                .try_into()
+                .context("Image width doesn't fit in the container header")?,
+            height: gray_image
+                .height()
+                .try_into()
+                .context("Image height doesn't fit in the container header")?,
+        };
+        let mut with_header = Vec::with_capacity(container::HEADER_LEN + packed.len());
+        container::write_header(&header, &mut with_header)
+            .context("Failed to write container header")?;
+        with_header.extend_from_slice(&packed);
+        with_header
     } else {
-        let file = File::create(&args.output)
-            .with_context(|| anyhow!("Failed to open output file: {:?}", args.output))?;
-        Box::new(file)
+        packed
     };
 
-    // Process image
-    process_image(&gray_image, &mut output, &Settings::from(&args))?;
+    match args.format {
+        OutputFormat::Raw => {
+            output
+                .write_all(&packed)
+                .context("Failed to write output file")?;
+        }
+        format => {
+            // Row-based wrapping only makes sense for the uncompressed,
+            // byte-aligned horizontal bitstream with no header offsetting it
+            let row_stride = (args.compression == Compression::None
+                && args.layout == Layout::Horizontal
+                && !args.header
+                && !settings.no_flush_after_pixel_row)
+                .then(|| gray_image.width().div_ceil(8) as usize);
+            codegen::write(
+                &packed,
+                gray_image.width(),
+                gray_image.height(),
+                row_stride,
+                &args.name,
+                format,
+                &mut output,
+            )
+            .context("Failed to write output file")?;
+        }
+    }
 
     Ok(())
 }
 
+/// Open `path` for writing (truncating it), treating `-` as stdout.
+fn open_output(path: &Path) -> Result<Box<dyn Write>> {
+    if path == Path::new("-") {
+        Ok(Box::new(io::stdout()))
+    } else {
+        let file = File::create(path)
+            .with_context(|| anyhow!("Failed to open output file: {:?}", path))?;
+        Ok(Box::new(file))
+    }
+}
+
+/// Read the entirety of `path` into `buf`, treating `-` as stdin.
+fn read_input(path: &Path, buf: &mut Vec<u8>) -> Result<()> {
+    if path == Path::new("-") {
+        io::stdin()
+            .read_to_end(buf)
+            .context("Failed to read from stdin")?;
+    } else {
+        File::open(path)
+            .with_context(|| anyhow!("Failed to open input file: {:?}", path))?
+            .read_to_end(buf)
+            .with_context(|| anyhow!("Failed to read input file: {:?}", path))?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -186,55 +296,14 @@ mod tests {
             Self {
                 threshold: 100,
                 no_flush_after_pixel_row: false,
+                dither: Dither::None,
+                serpentine: false,
+                layout: Layout::Horizontal,
+                bit_order: BitOrder::Msb,
             }
         }
     }
 
-    #[test]
-    fn test_all_true() {
-        let mut p = Pack::new(Vec::new());
-        for _ in 0..16 {
-            p.add(1).unwrap();
-        }
-        p.flush().unwrap();
-        assert_eq!(p.into_inner(), &[0xFF, 0xFF]);
-    }
-
-    #[test]
-    fn test_all_false() {
-        let mut p = Pack::new(Vec::new());
-        for _ in 0..16 {
-            p.add(0).unwrap();
-        }
-        p.flush().unwrap();
-        assert_eq!(p.into_inner(), &[0x00, 0x00]);
-    }
-
-    #[test]
-    fn test_some_true() {
-        let mut p = Pack::new(Vec::new());
-        for _ in 0..16 {
-            p.add(1).unwrap();
-            p.add(0).unwrap();
-        }
-        p.flush().unwrap();
-        assert_eq!(p.into_inner(), &[0xAA, 0xAA, 0xAA, 0xAA]);
-    }
-
-    #[test]
-    fn test_unaligned_pixels() {
-        let mut p = Pack::new(Vec::new());
-        for _ in 0..30 {
-            p.add(1).unwrap();
-            p.add(0).unwrap();
-        }
-        p.flush().unwrap();
-        assert_eq!(
-            p.into_inner(),
-            &[0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xA0]
-        );
-    }
-
     #[test]
     fn test_convert_bike_png() {
         let png = b"\
@@ -293,4 +362,65 @@ AAy2Yn26+qMSAAAAAElFTkSuQmCC";
             ]
         );
     }
+
+    #[cfg(feature = "embedded")]
+    fn assert_unpacks_to_source(png: &[u8], no_flush_after_pixel_row: bool) {
+        let png = data_encoding::BASE64.decode(png).unwrap();
+        let image = load_image(io::Cursor::new(png)).unwrap();
+        let settings = Settings {
+            no_flush_after_pixel_row,
+            ..Settings::default()
+        };
+
+        let mut output = Vec::new();
+        process_image(&image, &mut output, &settings).unwrap();
+
+        let expected = image
+            .pixels()
+            .map(|px| px.0[0] > settings.threshold)
+            .collect::<Vec<_>>();
+        let unpacked = embedded_mono_img::embedded::UnpackReader::new(
+            &output,
+            image.width(),
+            image.height(),
+            settings.no_flush_after_pixel_row,
+        )
+        .collect::<Vec<_>>();
+        assert_eq!(unpacked, expected);
+    }
+
+    #[cfg(feature = "embedded")]
+    #[test]
+    fn test_unpack_bike_png() {
+        let png = b"\
+iVBORw0KGgoAAAANSUhEUgAAABgAAAAOCAQAAACf8RT1AAABI2lDQ1BJQ0MgcHJvZmlsZQAAKJGd\
+kLFKw1AUhr+mRUUUBMVBHDI4CR3t5GBVCEKFWCsYndKkxWJuDElK8Q18E32YDoLgO7gqOPvf6OBg\
+Fi8c/o/DOf9/7wXHTSJTtA7ApGXu9bvBZXDlLr7h0GKNXZphVGRd3+9Rez5faVh9aVuv+rk/z0I8\
+KiLpXJVGWV5CY1/cmZWZZRUbt4P+kfhB7MYmjcVP4p3YxJbtbt8k0+jH095mZZRenNu+ahuPE07x\
+cRkyZUJCSVuaqnNMhz2pR07IPQWRNGGk3kwzJTeiQk4eh6KBSLepyduq8nylDOUxkZdNuMPI0+Zh\
+//d77eOs2mxszrMwD6tWU+WMx/D+CKsBrD/D8nVN1tLvt9XMdKqZf77xC9hLUFyVMfiXAAAAAmJL\
+R0QAAKqNIzIAAAAJcEhZcwAACxMAAAsTAQCanBgAAACtSURBVCjPrZLBCsIwEETfplJQQcSL//93\
+eqkXEbFpx0PSZGmrIJhDSHYzszNDDPHT2nxuJSabVQP8YcId2CO0wrcyoScQANFgRdq0bLorq1U+\
+DTQ82GWILQFTOdI4vlg021KSIeSejxgnRMd1OUGZ48bR8T8Z2BZOqwAVcy/aWQTVQ9pDtWRAC4zZ\
+UQR6fD/pUM1APgOn2fdDDerCATgDscAMMHqX4sz0t3+V+m/WOjn9Gzyk1gAAAABJRU5ErkJggg==";
+        assert_unpacks_to_source(png, false);
+        assert_unpacks_to_source(png, true);
+    }
+
+    #[cfg(feature = "embedded")]
+    #[test]
+    fn test_unpack_not_multiple_of_8() {
+        let png = b"\
+iVBORw0KGgoAAAANSUhEUgAAAB4AAAAeCAQAAACROWYpAAAAAXNSR0IB2cksfwAAAARnQU1BAACx\
+jwv8YQUAAAAgY0hSTQAAeiYAAICEAAD6AAAAgOgAAHUwAADqYAAAOpgAABdwnLpRPAAAAAJiS0dE\
+AACqjSMyAAAACXBIWXMAAC4jAAAuIwF4pT92AAABOElEQVQ4y2Nk+M9ANmBiYBg2mpUYvjH8R4K5\
+pGguYeBE4QfhtPs/Jrz2HxV8/M/7H5s6LJo9///5jw66idW85j8muECs5qdwLU/grF//rbFoxgiw\
+HAYpOHsSww8oi5Uhg5gA2we37dV/hv9n4bx7hG3mZTCGs88xMDDsh/MUGGIJ2dyFFEiZ/xn+y/z/\
+CudvIxRg5+FKn0NFTsJF3uB3tgWDFpx9Fkrvg4sIM9Tic/YiJEcnQcXE/n+Gi53A5+x7cGVPkUSP\
+wUW//VfDpTnm/7//hMAMXH6OYmAkmIPtcfn59X/C4M9/X2w21zKIEFF2MDMkYLP5OJLpThgx+gIu\
++wwzwNT+f4NLX8eSircgOT0P3dmFSAXPESzOXY2jUPrP8J/h/3WknGuKNeM/hqv49F8MKsY4AmsM\
+AAy2Yn26+qMSAAAAAElFTkSuQmCC";
+        assert_unpacks_to_source(png, false);
+        assert_unpacks_to_source(png, true);
+    }
 }
@@ -0,0 +1,154 @@
+//! Bit packing: how pixels are grouped into bytes and in what order.
+
+use crate::errors::*;
+use clap::ValueEnum;
+
+/// Whether the first bit written to a byte becomes its most- or
+/// least-significant bit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum BitOrder {
+    /// The first bit packed into a byte becomes its most significant bit
+    #[default]
+    Msb,
+    /// The first bit packed into a byte becomes its least significant bit
+    Lsb,
+}
+
+/// How pixels are grouped into bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Layout {
+    /// 8 pixels across a row become one byte (the default)
+    #[default]
+    Horizontal,
+    /// SSD1306/SH1106-style page addressing: each byte holds 8 vertically
+    /// stacked pixels of one column within an 8-row page. Defaults to LSB
+    /// bit order (bit 0 = top row of the page), matching GDDRAM layout.
+    #[value(name = "vertical-pages")]
+    VerticalPages,
+}
+
+/// Accumulates bits into bytes and writes them to `writer` as they complete.
+pub struct Pack<W> {
+    writer: W,
+    bit_order: BitOrder,
+    bits: [u8; 8],
+    ctr: usize,
+}
+
+impl<W: std::io::Write> Pack<W> {
+    pub fn new(writer: W, bit_order: BitOrder) -> Self {
+        Pack {
+            writer,
+            bit_order,
+            bits: Default::default(),
+            ctr: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.bits = Default::default();
+        self.ctr = 0;
+    }
+
+    fn to_byte(&self) -> u8 {
+        let mut byte = 0;
+        for (i, bit) in self.bits.iter().enumerate() {
+            let shift = match self.bit_order {
+                BitOrder::Msb => 7 - i,
+                BitOrder::Lsb => i,
+            };
+            byte |= bit << shift;
+        }
+        byte
+    }
+
+    fn write(&mut self) -> Result<()> {
+        let byte = self.to_byte();
+        debug!("Writing byte to file: 0x{byte:02X}");
+        self.writer.write_all(&[byte])?;
+        self.clear();
+        Ok(())
+    }
+
+    pub fn add(&mut self, bit: u8) -> Result<()> {
+        self.bits[self.ctr] = bit;
+        self.ctr += 1;
+        if self.ctr >= self.bits.len() {
+            self.write()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        if self.ctr == 0 {
+            return Ok(());
+        }
+        debug!("Padding incomplete byte with false-y bits");
+        self.write()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_true() {
+        let mut p = Pack::new(Vec::new(), BitOrder::Msb);
+        for _ in 0..16 {
+            p.add(1).unwrap();
+        }
+        p.flush().unwrap();
+        assert_eq!(p.into_inner(), &[0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_all_false() {
+        let mut p = Pack::new(Vec::new(), BitOrder::Msb);
+        for _ in 0..16 {
+            p.add(0).unwrap();
+        }
+        p.flush().unwrap();
+        assert_eq!(p.into_inner(), &[0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_some_true() {
+        let mut p = Pack::new(Vec::new(), BitOrder::Msb);
+        for _ in 0..16 {
+            p.add(1).unwrap();
+            p.add(0).unwrap();
+        }
+        p.flush().unwrap();
+        assert_eq!(p.into_inner(), &[0xAA, 0xAA, 0xAA, 0xAA]);
+    }
+
+    #[test]
+    fn test_unaligned_pixels() {
+        let mut p = Pack::new(Vec::new(), BitOrder::Msb);
+        for _ in 0..30 {
+            p.add(1).unwrap();
+            p.add(0).unwrap();
+        }
+        p.flush().unwrap();
+        assert_eq!(
+            p.into_inner(),
+            &[0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xA0]
+        );
+    }
+
+    #[test]
+    fn test_lsb_bit_order() {
+        let mut p = Pack::new(Vec::new(), BitOrder::Lsb);
+        for _ in 0..8 {
+            p.add(1).unwrap();
+            p.add(0).unwrap();
+        }
+        p.flush().unwrap();
+        assert_eq!(p.into_inner(), &[0x55, 0x55]);
+    }
+}